@@ -3,18 +3,52 @@ use std::collections::HashMap;
 pub type AttrMap = HashMap<String, String>;
 
 pub struct ElementData {
-    tag_name: String,
-    attributes: AttrMap,
+    pub(crate) tag_name: String,
+    pub(crate) attributes: AttrMap,
 }
 
 pub enum NodeType {
     Text(String),
     Element(ElementData),
+    Comment(String),
+    Doctype(String),
 }
 
 pub struct Node {
-    children: Vec<Node>,
-    node_type: NodeType,
+    pub(crate) children: Vec<Node>,
+    pub(crate) node_type: NodeType,
+}
+
+impl Node {
+    pub fn query_selector(&self, sel: &str) -> Option<&Node> {
+        self.query_selector_all(sel).into_iter().next()
+    }
+
+    pub fn query_selector_all(&self, sel: &str) -> Vec<&Node> {
+        let selector = Selector::parse(sel);
+        let mut results = Vec::new();
+        self.collect_matches(&selector, &Vec::new(), &mut results);
+        results
+    }
+
+    fn collect_matches<'a>(
+        &'a self,
+        selector: &Selector,
+        ancestors: &[&'a Node],
+        results: &mut Vec<&'a Node>,
+    ) {
+        if let NodeType::Element(ref data) = self.node_type {
+            if selector.matches(data, ancestors) {
+                results.push(self);
+            }
+        }
+
+        let mut descendant_ancestors = ancestors.to_vec();
+        descendant_ancestors.push(self);
+        for child in &self.children {
+            child.collect_matches(selector, &descendant_ancestors, results);
+        }
+    }
 }
 
 pub fn text(data: String) -> Node {
@@ -24,7 +58,21 @@ pub fn text(data: String) -> Node {
     }
 }
 
-pub fn element(name: String, attrs: AttrMap, children: Vec<Node>) -> Node {
+pub fn comment(data: String) -> Node {
+    Node {
+        children: Vec::new(),
+        node_type: NodeType::Comment(data),
+    }
+}
+
+pub fn doctype(data: String) -> Node {
+    Node {
+        children: Vec::new(),
+        node_type: NodeType::Doctype(data),
+    }
+}
+
+pub fn elem(name: String, attrs: AttrMap, children: Vec<Node>) -> Node {
     Node {
         children,
         node_type: NodeType::Element(ElementData {
@@ -33,3 +81,424 @@ pub fn element(name: String, attrs: AttrMap, children: Vec<Node>) -> Node {
         }),
     }
 }
+
+// &amp; や &#169; のような文字参照をデコードする
+pub fn decode_entities(input: &str) -> String {
+    let chars: Vec<char> = input.chars().collect();
+    let mut result = String::with_capacity(input.len());
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] != '&' {
+            result.push(chars[i]);
+            i += 1;
+            continue;
+        }
+
+        // ';' を探す範囲は長すぎる参照を避けるため制限する
+        let search_end = std::cmp::min(chars.len(), i + 1 + 32);
+        let semicolon = (i + 1..search_end).find(|&j| chars[j] == ';');
+
+        let decoded = semicolon.and_then(|end| {
+            let body: String = chars[i + 1..end].iter().collect();
+            decode_entity_body(&body).map(|ch| (ch, end))
+        });
+
+        match decoded {
+            Some((ch, end)) => {
+                result.push(ch);
+                i = end + 1;
+            }
+            None => {
+                // 不正な参照はそのまま `&` として出力し、次の文字から再開する
+                result.push('&');
+                i += 1;
+            }
+        }
+    }
+    result
+}
+
+fn decode_entity_body(body: &str) -> Option<char> {
+    if let Some(digits) = body.strip_prefix('#') {
+        if let Some(hex) = digits.strip_prefix('x').or_else(|| digits.strip_prefix('X')) {
+            u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+        } else {
+            digits.parse::<u32>().ok().and_then(char::from_u32)
+        }
+    } else {
+        named_entity(body)
+    }
+}
+
+fn named_entity(name: &str) -> Option<char> {
+    match name {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some('\u{00A0}'),
+        "copy" => Some('\u{00A9}'),
+        "reg" => Some('\u{00AE}'),
+        "trade" => Some('\u{2122}'),
+        "mdash" => Some('\u{2014}'),
+        "ndash" => Some('\u{2013}'),
+        "hellip" => Some('\u{2026}'),
+        "laquo" => Some('\u{00AB}'),
+        "raquo" => Some('\u{00BB}'),
+        "euro" => Some('\u{20AC}'),
+        _ => None,
+    }
+}
+
+// 閉じタグを持たないHTML要素 (void elements) か判定する
+pub(crate) fn is_void_element(tag_name: &str) -> bool {
+    matches!(
+        tag_name.to_ascii_lowercase().as_str(),
+        "area"
+            | "base"
+            | "br"
+            | "col"
+            | "embed"
+            | "hr"
+            | "img"
+            | "input"
+            | "link"
+            | "meta"
+            | "param"
+            | "source"
+            | "track"
+            | "wbr"
+    )
+}
+
+// ノードをHTML文字列へ変換する (html::parse の逆変換)
+pub fn serialize(node: &Node) -> String {
+    let mut result = String::new();
+    serialize_node(node, &mut result);
+    result
+}
+
+fn serialize_node(node: &Node, out: &mut String) {
+    match &node.node_type {
+        NodeType::Text(data) => out.push_str(&escape_text(data)),
+        NodeType::Comment(data) => {
+            out.push_str("<!--");
+            out.push_str(data);
+            out.push_str("-->");
+        }
+        NodeType::Doctype(data) => {
+            out.push_str("<!DOCTYPE");
+            out.push_str(data);
+            out.push('>');
+        }
+        NodeType::Element(element) => serialize_element(element, &node.children, out),
+    }
+}
+
+fn serialize_element(element: &ElementData, children: &[Node], out: &mut String) {
+    out.push('<');
+    out.push_str(&element.tag_name);
+
+    // 属性は決定論的な出力にするためキー順にソートする
+    let mut names: Vec<&String> = element.attributes.keys().collect();
+    names.sort();
+    for name in names {
+        let value = &element.attributes[name];
+        out.push(' ');
+        out.push_str(name);
+        out.push_str("=\"");
+        out.push_str(&escape_attr_value(value));
+        out.push('"');
+    }
+
+    if is_void_element(&element.tag_name) {
+        out.push('>');
+        return;
+    }
+
+    out.push('>');
+    // raw-text要素の中身はパース時にデコードしていないので、エスケープせずにそのまま書き戻す
+    let raw_text = crate::html::is_raw_text_element(&element.tag_name);
+    for child in children {
+        match &child.node_type {
+            NodeType::Text(data) if raw_text => out.push_str(data),
+            _ => serialize_node(child, out),
+        }
+    }
+    out.push_str("</");
+    out.push_str(&element.tag_name);
+    out.push('>');
+}
+
+fn escape_text(data: &str) -> String {
+    data.chars().fold(String::with_capacity(data.len()), |mut acc, c| {
+        match c {
+            '&' => acc.push_str("&amp;"),
+            '<' => acc.push_str("&lt;"),
+            '>' => acc.push_str("&gt;"),
+            _ => acc.push(c),
+        }
+        acc
+    })
+}
+
+fn escape_attr_value(data: &str) -> String {
+    data.chars().fold(String::with_capacity(data.len()), |mut acc, c| {
+        match c {
+            '&' => acc.push_str("&amp;"),
+            '<' => acc.push_str("&lt;"),
+            '>' => acc.push_str("&gt;"),
+            '"' => acc.push_str("&quot;"),
+            _ => acc.push(c),
+        }
+        acc
+    })
+}
+
+// `div .item a` のような、空白区切りの子孫セレクタの並び
+struct Selector {
+    components: Vec<SimpleSelector>,
+}
+
+// タグ名・クラス・id・属性を組み合わせた単一のセレクタ (`div.item#id[attr="val"]`)
+struct SimpleSelector {
+    tag_name: Option<String>,
+    id: Option<String>,
+    classes: Vec<String>,
+    attrs: Vec<(String, Option<String>)>,
+}
+
+impl Selector {
+    fn parse(input: &str) -> Selector {
+        Selector {
+            components: input.split_whitespace().map(SimpleSelector::parse).collect(),
+        }
+    }
+
+    // `self` がこのセレクタに一致するかを、先祖のリスト (ルートに近いものが先頭) を使って判定する
+    fn matches(&self, data: &ElementData, ancestors: &[&Node]) -> bool {
+        let (last, rest) = match self.components.split_last() {
+            Some(parts) => parts,
+            None => return false,
+        };
+        if !last.matches(data) {
+            return false;
+        }
+        if rest.is_empty() {
+            return true;
+        }
+
+        // 残りのコンポーネントは、近い祖先から順に貪欲に一致させていく
+        let mut remaining = rest.len();
+        for ancestor in ancestors.iter().rev() {
+            if remaining == 0 {
+                break;
+            }
+            if let NodeType::Element(ref ancestor_data) = ancestor.node_type {
+                if rest[remaining - 1].matches(ancestor_data) {
+                    remaining -= 1;
+                }
+            }
+        }
+        remaining == 0
+    }
+}
+
+impl SimpleSelector {
+    fn parse(input: &str) -> SimpleSelector {
+        let chars: Vec<char> = input.chars().collect();
+        let mut selector = SimpleSelector {
+            tag_name: None,
+            id: None,
+            classes: Vec::new(),
+            attrs: Vec::new(),
+        };
+        let mut i = 0;
+
+        if i < chars.len() && !matches!(chars[i], '.' | '#' | '[') {
+            let start = i;
+            while i < chars.len() && !matches!(chars[i], '.' | '#' | '[') {
+                i += 1;
+            }
+            let name: String = chars[start..i].iter().collect();
+            if name != "*" {
+                selector.tag_name = Some(name);
+            }
+        }
+
+        while i < chars.len() {
+            match chars[i] {
+                '.' => {
+                    i += 1;
+                    let start = i;
+                    while i < chars.len() && !matches!(chars[i], '.' | '#' | '[') {
+                        i += 1;
+                    }
+                    selector.classes.push(chars[start..i].iter().collect());
+                }
+                '#' => {
+                    i += 1;
+                    let start = i;
+                    while i < chars.len() && !matches!(chars[i], '.' | '#' | '[') {
+                        i += 1;
+                    }
+                    selector.id = Some(chars[start..i].iter().collect());
+                }
+                '[' => {
+                    i += 1;
+                    let start = i;
+                    while i < chars.len() && chars[i] != ']' {
+                        i += 1;
+                    }
+                    let body: String = chars[start..i].iter().collect();
+                    if i < chars.len() {
+                        i += 1; // ']' を読み飛ばす
+                    }
+                    selector.attrs.push(parse_attr_selector(&body));
+                }
+                _ => i += 1,
+            }
+        }
+
+        selector
+    }
+
+    fn matches(&self, data: &ElementData) -> bool {
+        if let Some(ref tag_name) = self.tag_name {
+            if !data.tag_name.eq_ignore_ascii_case(tag_name) {
+                return false;
+            }
+        }
+
+        if let Some(ref id) = self.id {
+            if data.attributes.get("id") != Some(id) {
+                return false;
+            }
+        }
+
+        for class in &self.classes {
+            let has_class = data
+                .attributes
+                .get("class")
+                .map(|classes| classes.split_whitespace().any(|c| c == class))
+                .unwrap_or(false);
+            if !has_class {
+                return false;
+            }
+        }
+
+        for (name, expected) in &self.attrs {
+            match data.attributes.get(name) {
+                None => return false,
+                Some(actual) => {
+                    if let Some(expected) = expected {
+                        if actual != expected {
+                            return false;
+                        }
+                    }
+                }
+            }
+        }
+
+        true
+    }
+}
+
+// `[attr]` または `[attr="value"]` の中身を解析する
+fn parse_attr_selector(body: &str) -> (String, Option<String>) {
+    match body.find('=') {
+        None => (body.trim().to_string(), None),
+        Some(eq) => {
+            let name = body[..eq].trim().to_string();
+            let mut value = body[eq + 1..].trim();
+            if value.len() >= 2
+                && ((value.starts_with('"') && value.ends_with('"'))
+                    || (value.starts_with('\'') && value.ends_with('\'')))
+            {
+                value = &value[1..value.len() - 1];
+            }
+            (name, Some(value.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_named_hex_and_decimal_references() {
+        assert_eq!(decode_entities("a &amp; b"), "a & b");
+        assert_eq!(decode_entities("&#169;"), "\u{00A9}");
+        assert_eq!(decode_entities("&#x2122;"), "\u{2122}");
+    }
+
+    #[test]
+    fn leaves_malformed_or_unknown_references_untouched() {
+        // ';' が見つからない
+        assert_eq!(decode_entities("a & b"), "a & b");
+        // 未知の名前付き参照
+        assert_eq!(decode_entities("&notareal;"), "&notareal;");
+        // 不正な数値参照
+        assert_eq!(decode_entities("&#zzz;"), "&#zzz;");
+    }
+
+    #[test]
+    fn bounds_entity_scan_to_avoid_runaway_search() {
+        // ';' が32文字より先にしかない場合はエンティティとして扱わない
+        let long_body = "a".repeat(40);
+        let input = format!("&{};", long_body);
+        assert_eq!(decode_entities(&input), input);
+    }
+
+    fn elem_with_attrs(name: &str, attrs: &[(&str, &str)], children: Vec<Node>) -> Node {
+        let attributes = attrs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        elem(name.to_string(), attributes, children)
+    }
+
+    #[test]
+    fn matches_tag_class_and_id_selectors() {
+        let tree = elem_with_attrs(
+            "div",
+            &[("id", "main"), ("class", "a b")],
+            vec![elem_with_attrs("span", &[("class", "b")], vec![])],
+        );
+
+        assert!(tree.query_selector("div#main").is_some());
+        assert!(tree.query_selector("div.a.b").is_some());
+        assert!(tree.query_selector("span.b").is_some());
+        assert!(tree.query_selector("span.missing").is_none());
+        assert!(tree.query_selector("#nope").is_none());
+    }
+
+    #[test]
+    fn matches_greedy_descendant_selector() {
+        // `div span` は、直接の親子関係ではなく祖先のどこかに div があれば一致する
+        let tree = elem_with_attrs(
+            "div",
+            &[],
+            vec![elem_with_attrs(
+                "p",
+                &[],
+                vec![elem_with_attrs("span", &[], vec![])],
+            )],
+        );
+
+        assert!(tree.query_selector("div span").is_some());
+        assert!(tree.query_selector("p span").is_some());
+        // `section span` は祖先の中に section が無いため一致しない
+        assert!(tree.query_selector("section span").is_none());
+    }
+
+    #[test]
+    fn matches_attribute_selector_with_and_without_value() {
+        let tree = elem_with_attrs("a", &[("href", "/x")], vec![]);
+        assert!(tree.query_selector("a[href]").is_some());
+        assert!(tree.query_selector("a[href=\"/x\"]").is_some());
+        assert!(tree.query_selector("a[href=\"/y\"]").is_none());
+    }
+}
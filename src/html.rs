@@ -1,6 +1,49 @@
 use crate::dom;
-use dom::Node;
 use std::collections::HashMap;
+use std::fmt;
+
+// パース失敗時のエラー位置・種類・周辺テキストを保持する
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub pos: usize,
+    pub kind: ParseErrorKind,
+    pub context: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseErrorKind {
+    UnexpectedEof,
+    ExpectedChar(char),
+    MismatchedTag { expected: String, found: String },
+    InvalidAttrValue,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ParseErrorKind::UnexpectedEof => {
+                write!(f, "unexpected end of input at byte {} (near \"{}\")", self.pos, self.context)
+            }
+            ParseErrorKind::ExpectedChar(c) => write!(
+                f,
+                "expected '{}' at byte {} (near \"{}\")",
+                c, self.pos, self.context
+            ),
+            ParseErrorKind::MismatchedTag { expected, found } => write!(
+                f,
+                "mismatched closing tag at byte {}: expected </{}> but found </{}> (near \"{}\")",
+                self.pos, expected, found, self.context
+            ),
+            ParseErrorKind::InvalidAttrValue => write!(
+                f,
+                "invalid attribute value at byte {} (near \"{}\")",
+                self.pos, self.context
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
 
 struct Parser {
     pos: usize,
@@ -9,8 +52,11 @@ struct Parser {
 
 impl Parser {
     // 現在の文字を読み取る
-    fn next_char(&self) -> char {
-        self.input[self.pos..].chars().next().unwrap()
+    fn next_char(&self) -> Result<char, ParseError> {
+        self.input[self.pos..]
+            .chars()
+            .next()
+            .ok_or_else(|| self.error(self.pos, ParseErrorKind::UnexpectedEof))
     }
 
     // 文字が与えられた文字列で始まるか
@@ -25,130 +71,344 @@ impl Parser {
 
     // 現在の文字を返す
     // self.pos の値を1文字分ずらす
-    fn consume_char(&mut self) -> char {
+    fn consume_char(&mut self) -> Result<char, ParseError> {
         // マルチバイト文字を処理できるようにする
         let mut iter = self.input[self.pos..].char_indices();
-        let (_, cur_char) = iter.next().unwrap();
-        let (next_pos, _) = iter.next().unwrap_or((1, ' '));
+        let (_, cur_char) = iter
+            .next()
+            .ok_or_else(|| self.error(self.pos, ParseErrorKind::UnexpectedEof))?;
+        let (next_pos, _) = iter.next().unwrap_or((cur_char.len_utf8(), ' '));
         self.pos += next_pos;
-        cur_char
+        Ok(cur_char)
+    }
+
+    // 次の文字が期待したものであることを確認して読み飛ばす
+    fn expect_char(&mut self, expected: char) -> Result<(), ParseError> {
+        let start = self.pos;
+        let actual = self.consume_char()?;
+        if actual == expected {
+            Ok(())
+        } else {
+            Err(self.error(start, ParseErrorKind::ExpectedChar(expected)))
+        }
     }
 
     // false を返すまで文字を解析します
-    fn consume_while<F>(&mut self, test: F) -> String
+    fn consume_while<F>(&mut self, test: F) -> Result<String, ParseError>
     where
         F: Fn(char) -> bool,
     {
         let mut result = String::new();
-        while !self.eof() && test(self.next_char()) {
-            result.push(self.consume_char());
+        while !self.eof() && test(self.next_char()?) {
+            result.push(self.consume_char()?);
         }
-        result
+        Ok(result)
     }
 
     // スペース文字を無視
-    fn consume_whitespace(&mut self) {
-        self.consume_while(char::is_whitespace);
+    fn consume_whitespace(&mut self) -> Result<(), ParseError> {
+        self.consume_while(char::is_whitespace)?;
+        Ok(())
     }
 
     // タグまたは属性名を解析
-    fn parse_tag_name(&mut self) -> String {
-        self.consume_while(|c| match c {
-            'a'..='z' | 'A'..='Z' | '0'..='9' => true,
-            _ => false,
-        })
+    fn parse_tag_name(&mut self) -> Result<String, ParseError> {
+        self.consume_while(|c| matches!(c, 'a'..='z' | 'A'..='Z' | '0'..='9'))
     }
 
     // nodeが1件の場合
-    fn parse_node(&mut self) -> dom::Node {
-        match self.next_char() {
-            '<' => self.parse_element(),
-            _ => self.parse_text(),
+    fn parse_node(&mut self) -> Result<dom::Node, ParseError> {
+        if self.starts_with("<!--") {
+            self.parse_comment()
+        } else if self.starts_with_ignore_ascii_case("<!doctype") {
+            self.parse_doctype()
+        } else if self.starts_with("<![CDATA[") {
+            self.parse_cdata()
+        } else {
+            match self.next_char()? {
+                '<' => self.parse_element(),
+                _ => self.parse_text(),
+            }
+        }
+    }
+
+    // `closing_start` (例: "</script") に一致し、かつその直後が空白・'/'・'>'・EOF
+    // であるかを調べる。境界チェックをしないと、要素内のテキストに `</scriptological`
+    // のような文字列が含まれるだけで閉じタグと誤認識してしまう
+    fn at_closing_tag(&self, closing_start: &str) -> bool {
+        if !self.starts_with_ignore_ascii_case(closing_start) {
+            return false;
+        }
+        match self.input[self.pos + closing_start.len()..].chars().next() {
+            None => true,
+            Some(c) => matches!(c, '\t' | '\n' | '\x0C' | ' ' | '/' | '>'),
+        }
+    }
+
+    // 入力が (大文字小文字を区別せずに) 与えられた文字列で始まるか
+    fn starts_with_ignore_ascii_case(&self, s: &str) -> bool {
+        let rest = &self.input[self.pos..];
+        rest.len() >= s.len() && rest[..s.len()].eq_ignore_ascii_case(s)
+    }
+
+    // 次に現れる `end` の直前までを読み取り、`end` 自身も読み飛ばす
+    fn consume_until(&mut self, end: &str) -> Result<String, ParseError> {
+        let mut result = String::new();
+        while !self.eof() && !self.starts_with(end) {
+            result.push(self.consume_char()?);
+        }
+        for _ in 0..end.len() {
+            if !self.eof() {
+                self.consume_char()?;
+            }
+        }
+        Ok(result)
+    }
+
+    // <!-- ... --> を解析する
+    fn parse_comment(&mut self) -> Result<dom::Node, ParseError> {
+        for _ in 0.."<!--".len() {
+            self.consume_char()?;
+        }
+        Ok(dom::comment(self.consume_until("-->")?))
+    }
+
+    // <!DOCTYPE ...> を解析する
+    fn parse_doctype(&mut self) -> Result<dom::Node, ParseError> {
+        // "DOCTYPE" キーワード自体は読み飛ばし、残り (例: " html") だけを保持する。
+        // キーワードまで含めてしまうと、serialize側が付け足す "<!DOCTYPE" と
+        // 二重になってしまう
+        for _ in 0.."<!doctype".len() {
+            self.consume_char()?;
+        }
+        Ok(dom::doctype(self.consume_until(">")?))
+    }
+
+    // <![CDATA[ ... ]]> を解析する
+    fn parse_cdata(&mut self) -> Result<dom::Node, ParseError> {
+        for _ in 0.."<![CDATA[".len() {
+            self.consume_char()?;
+        }
+        Ok(dom::text(self.consume_until("]]>")?))
+    }
+
+    // raw-text要素の中身を、対応する閉じタグの直前までそのまま読み取る
+    fn consume_raw_text(&mut self, tag_name: &str) -> Result<String, ParseError> {
+        let closing_start = format!("</{}", tag_name);
+        let mut result = String::new();
+        while !self.eof() && !self.at_closing_tag(&closing_start) {
+            result.push(self.consume_char()?);
+        }
+        // 閉じタグ </tagname ... > を読み飛ばす
+        while !self.eof() && self.next_char()? != '>' {
+            self.consume_char()?;
+        }
+        if !self.eof() {
+            self.consume_char()?;
         }
+        Ok(result)
     }
 
     // text nodeが1件の場合
-    fn parse_text(&mut self) -> dom::Node {
-        dom::text(self.consume_while(|c| c != '<'))
+    fn parse_text(&mut self) -> Result<dom::Node, ParseError> {
+        let raw = self.consume_while(|c| c != '<')?;
+        Ok(dom::text(dom::decode_entities(&raw)))
     }
 
     // タグを解析する <, >, /, tag_name, attribute
-    fn parse_element(&mut self) -> dom::Node {
+    fn parse_element(&mut self) -> Result<dom::Node, ParseError> {
         // < を読み取り、次の文字を読み取る
-        assert!(self.consume_char() == '<');
-        let tag_name = self.parse_tag_name();
-        let attrs = self.parse_attributes();
-        assert!(self.consume_char() == '>');
+        self.expect_char('<')?;
+        let tag_name = self.parse_tag_name()?;
+        let attrs = self.parse_attributes()?;
+
+        // XML形式の自己終了タグ <tag/> は子要素・閉じタグを持たない
+        if self.starts_with("/>") {
+            self.consume_char()?;
+            self.consume_char()?;
+            return Ok(dom::elem(tag_name, attrs, Vec::new()));
+        }
+
+        self.expect_char('>')?;
+
+        // void要素 (<br> <img> など) は閉じタグを持たない
+        if dom::is_void_element(&tag_name) {
+            return Ok(dom::elem(tag_name, attrs, Vec::new()));
+        }
+
+        // raw-text要素 (<script> <style> など) の中身は子要素として解析せず、
+        // 閉じタグが現れるまでをそのままテキストとして扱う
+        if is_raw_text_element(&tag_name) {
+            let content = self.consume_raw_text(&tag_name)?;
+            return Ok(dom::elem(tag_name, attrs, vec![dom::text(content)]));
+        }
 
         // nodeの内容を解析
-        let children = self.parse_nodes();
+        let children = self.parse_nodes()?;
 
         // 閉じタグを読み取る
-        assert!(self.consume_char() == '<');
-        assert!(self.consume_char() == '/');
-        assert!(self.parse_tag_name() == tag_name);
-        assert!(self.consume_char() == '>');
+        let close_start = self.pos;
+        self.expect_char('<')?;
+        self.expect_char('/')?;
+        let closing_name = self.parse_tag_name()?;
+        if closing_name != tag_name {
+            return Err(self.error(
+                close_start,
+                ParseErrorKind::MismatchedTag {
+                    expected: tag_name,
+                    found: closing_name,
+                },
+            ));
+        }
+        self.expect_char('>')?;
 
-        dom::elem(tag_name, attrs, children)
+        Ok(dom::elem(tag_name, attrs, children))
     }
 
     // name="value" のペアを解析し、属性値として取得する
-    fn parse_attr(&mut self) -> (String, String) {
-        let name = self.parse_tag_name();
-        assert!(self.consume_char() == '=');
-        let value = self.parse_attr_value();
-        (name, value)
+    fn parse_attr(&mut self) -> Result<(String, String), ParseError> {
+        let name = self.parse_tag_name()?;
+        self.expect_char('=')?;
+        let value = self.parse_attr_value()?;
+        Ok((name, value))
     }
 
     // "" の中身
-    fn parse_attr_value(&mut self) -> String {
-        let open_quote = self.consume_char();
-        assert!(open_quote == '"' || open_quote == '\'');
-        let value = self.consume_while(|c| c != open_quote);
-        assert!(self.consume_char() == open_quote);
-        value
+    fn parse_attr_value(&mut self) -> Result<String, ParseError> {
+        let start = self.pos;
+        let open_quote = self.consume_char()?;
+        if open_quote != '"' && open_quote != '\'' {
+            return Err(self.error(start, ParseErrorKind::InvalidAttrValue));
+        }
+        let value = self.consume_while(|c| c != open_quote)?;
+        if self.eof() {
+            return Err(self.error(self.pos, ParseErrorKind::InvalidAttrValue));
+        }
+        self.consume_char()?;
+        Ok(dom::decode_entities(&value))
     }
 
     // 空白で区切られた属性を解析する
-    fn parse_attributes(&mut self) -> dom::AttrMap {
+    fn parse_attributes(&mut self) -> Result<dom::AttrMap, ParseError> {
         let mut attributes = HashMap::new();
         loop {
-            self.consume_whitespace();
-            if self.next_char() == '>' {
+            self.consume_whitespace()?;
+            if self.eof() {
+                return Err(self.error(self.pos, ParseErrorKind::UnexpectedEof));
+            }
+            if self.next_char()? == '>' || self.starts_with("/>") {
                 break;
             }
-            let (name, value) = self.parse_attr();
+            let (name, value) = self.parse_attr()?;
             attributes.insert(name, value);
         }
-        attributes
+        Ok(attributes)
     }
 
     // 子ノードを解析するために、閉じタグに到達するまでループを再帰的に実行する
-    fn parse_nodes(&mut self) -> Vec<dom::Node> {
+    fn parse_nodes(&mut self) -> Result<Vec<dom::Node>, ParseError> {
         let mut nodes = Vec::new();
         loop {
-            self.consume_whitespace();
+            self.consume_whitespace()?;
             if self.eof() || self.starts_with("</") {
                 break;
             }
-            nodes.push(self.parse_node());
+            nodes.push(self.parse_node()?);
+        }
+        Ok(nodes)
+    }
+
+    // pos の位置を指すエラーを、周辺テキストのスニペット付きで作成する
+    fn error(&self, pos: usize, kind: ParseErrorKind) -> ParseError {
+        ParseError {
+            pos,
+            kind,
+            context: self.context_snippet(pos),
+        }
+    }
+
+    // エラー箇所の前後を含む短いスニペットを取り出す (文字境界に丸める)
+    fn context_snippet(&self, pos: usize) -> String {
+        const WINDOW: usize = 16;
+        let pos = std::cmp::min(pos, self.input.len());
+
+        let mut start = pos.saturating_sub(WINDOW);
+        while start > 0 && !self.input.is_char_boundary(start) {
+            start -= 1;
         }
-        nodes
+
+        let mut end = std::cmp::min(self.input.len(), pos + WINDOW);
+        while end < self.input.len() && !self.input.is_char_boundary(end) {
+            end += 1;
+        }
+
+        self.input[start..end].to_string()
     }
 }
 
+// 子要素としてではなく、生のテキストとして内容を読み取るべき要素か判定する
+pub(crate) fn is_raw_text_element(tag_name: &str) -> bool {
+    matches!(
+        tag_name.to_ascii_lowercase().as_str(),
+        "script" | "style" | "textarea" | "title"
+    )
+}
+
 // HTMLを解析し、htmlタグを返却する
-pub fn parse(source: String) -> dom::Node {
-    let mut nodes = Parser {
+pub fn parse(source: String) -> Result<dom::Node, ParseError> {
+    let mut parser = Parser {
         pos: 0,
         input: source,
-    }
-    .parse_nodes();
+    };
+    let mut nodes = parser.parse_nodes()?;
 
     // htmlタグが存在しない場合は作成する
     if nodes.len() == 1 {
-        nodes.swap_remove(0)
+        Ok(nodes.swap_remove(0))
     } else {
-        dom::elem(String::from("html"), HashMap::new(), nodes)
+        Ok(dom::elem(String::from("html"), HashMap::new(), nodes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_void_raw_text_and_doctype() {
+        let source = "<!doctype html><p>hi<br>there</p><script>var x = 1;</script>";
+        let node = parse(source.to_string()).unwrap();
+        let out = dom::serialize(&node);
+        assert_eq!(
+            out,
+            "<!DOCTYPE html><p>hi<br>there</p><script>var x = 1;</script>"
+        );
+    }
+
+    #[test]
+    fn raw_text_stops_only_at_tag_boundary() {
+        let source = r#"<script>var x = "</scriptological";</script>"#;
+        let node = parse(source.to_string()).unwrap();
+        let out = dom::serialize(&node);
+        assert_eq!(out, source);
+    }
+
+    #[test]
+    fn reports_mismatched_closing_tag() {
+        let err = parse("<div><p>hi</div>".to_string()).unwrap_err();
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::MismatchedTag {
+                expected: String::from("p"),
+                found: String::from("div"),
+            }
+        );
+    }
+
+    #[test]
+    fn reports_unexpected_eof_with_position() {
+        let err = parse("<div>".to_string()).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::UnexpectedEof);
+        assert_eq!(err.pos, "<div>".len());
     }
 }
@@ -0,0 +1,136 @@
+use crate::dom::{Node, NodeType};
+use std::collections::HashMap;
+
+// クラス/id名が本文らしさ・ノイズらしさを示すパターン
+const POSITIVE_PATTERNS: &[&str] = &["article", "body", "content", "entry", "main", "post", "text"];
+const NEGATIVE_PATTERNS: &[&str] = &["comment", "footer", "nav", "sidebar", "ad", "promo", "sponsor"];
+
+// 段落らしいテキストを持つ要素とみなすタグ名
+const PARAGRAPH_TAGS: &[&str] = &["p", "pre", "td"];
+
+// 本文らしさが最も高いスコアの要素を返す (Mozilla Readability のスコアリングを簡略に移植)
+pub fn find_main_content(root: &Node) -> Option<&Node> {
+    let mut scores: HashMap<usize, f64> = HashMap::new();
+    score_candidates(root, &Vec::new(), &mut scores);
+
+    let mut best: Option<(&Node, f64)> = None;
+    pick_best(root, &scores, &mut best);
+    best.map(|(node, _)| node)
+}
+
+fn score_candidates<'a>(node: &'a Node, ancestors: &[&'a Node], scores: &mut HashMap<usize, f64>) {
+    if is_paragraph_like(node) {
+        let text = inner_text(node);
+        if text.chars().count() >= 25 {
+            let contribution = paragraph_contribution(&text);
+            if let Some(&parent) = ancestors.last() {
+                add_score(parent, contribution, scores);
+                if ancestors.len() >= 2 {
+                    let grandparent = ancestors[ancestors.len() - 2];
+                    add_score(grandparent, contribution / 2.0, scores);
+                }
+            }
+        }
+    }
+
+    let mut child_ancestors = ancestors.to_vec();
+    child_ancestors.push(node);
+    for child in &node.children {
+        score_candidates(child, &child_ancestors, scores);
+    }
+}
+
+fn is_paragraph_like(node: &Node) -> bool {
+    matches!(&node.node_type, NodeType::Element(data) if PARAGRAPH_TAGS.contains(&data.tag_name.to_ascii_lowercase().as_str()))
+}
+
+// 1 + カンマの数 + min(floor(文字数/100), 3)
+fn paragraph_contribution(text: &str) -> f64 {
+    let commas = text.matches(',').count() as f64;
+    let length_bonus = std::cmp::min(text.chars().count() / 100, 3) as f64;
+    1.0 + commas + length_bonus
+}
+
+fn add_score(node: &Node, amount: f64, scores: &mut HashMap<usize, f64>) {
+    let key = node_id(node);
+    let entry = scores.entry(key).or_insert_with(|| class_weight(node));
+    *entry += amount;
+}
+
+// ノードをポインタの同一性でスコアマップのキーにする
+fn node_id(node: &Node) -> usize {
+    node as *const Node as usize
+}
+
+// class/id が正または負のパターンに一致する場合の加点/減点
+fn class_weight(node: &Node) -> f64 {
+    let data = match &node.node_type {
+        NodeType::Element(data) => data,
+        _ => return 0.0,
+    };
+
+    let mut weight = 0.0;
+    for attr in ["class", "id"] {
+        let value = match data.attributes.get(attr) {
+            Some(value) => value.to_ascii_lowercase(),
+            None => continue,
+        };
+        if POSITIVE_PATTERNS.iter().any(|pattern| value.contains(pattern)) {
+            weight += 25.0;
+        }
+        if NEGATIVE_PATTERNS.iter().any(|pattern| value.contains(pattern)) {
+            weight -= 25.0;
+        }
+    }
+    weight
+}
+
+fn inner_text(node: &Node) -> String {
+    let mut text = String::new();
+    collect_text(node, &mut text);
+    text
+}
+
+fn collect_text(node: &Node, out: &mut String) {
+    match &node.node_type {
+        NodeType::Text(data) => out.push_str(data),
+        NodeType::Element(_) => {
+            for child in &node.children {
+                collect_text(child, out);
+            }
+        }
+        NodeType::Comment(_) | NodeType::Doctype(_) => {}
+    }
+}
+
+// <a> 要素の子孫に含まれるテキストの文字数を合計する
+fn link_text_len(node: &Node) -> usize {
+    match &node.node_type {
+        NodeType::Element(data) if data.tag_name.eq_ignore_ascii_case("a") => {
+            inner_text(node).chars().count()
+        }
+        NodeType::Element(_) => node.children.iter().map(link_text_len).sum(),
+        _ => 0,
+    }
+}
+
+// テキスト全体のうち、リンク内のテキストが占める割合
+fn link_density(node: &Node) -> f64 {
+    let total = inner_text(node).chars().count();
+    if total == 0 {
+        return 0.0;
+    }
+    link_text_len(node) as f64 / total as f64
+}
+
+fn pick_best<'a>(node: &'a Node, scores: &HashMap<usize, f64>, best: &mut Option<(&'a Node, f64)>) {
+    if let Some(&raw_score) = scores.get(&node_id(node)) {
+        let adjusted = raw_score * (1.0 - link_density(node));
+        if best.as_ref().is_none_or(|(_, current)| adjusted > *current) {
+            *best = Some((node, adjusted));
+        }
+    }
+    for child in &node.children {
+        pick_best(child, scores, best);
+    }
+}
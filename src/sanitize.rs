@@ -0,0 +1,262 @@
+use crate::dom::{self, AttrMap, Node, NodeType};
+use std::collections::{HashMap, HashSet};
+
+// 許可するタグ・属性・URLスキームのポリシーに従ってDOMツリーを無害化する
+pub struct Sanitizer {
+    allowed_tags: HashSet<String>,
+    allowed_attrs: HashMap<String, HashSet<String>>,
+    global_attrs: HashSet<String>,
+    allowed_schemes: HashSet<String>,
+    promote_disallowed_children: bool,
+    neutralize_src: bool,
+}
+
+impl Sanitizer {
+    pub fn builder() -> SanitizerBuilder {
+        SanitizerBuilder::new()
+    }
+
+    // ツリーを無害化した結果を返す。結果が1ノードならそのまま返し、
+    // ルート自体が許可されず残ったノードが0個または複数の場合は `div` でまとめて返す
+    pub fn sanitize(&self, root: &Node) -> Node {
+        let mut nodes = Vec::new();
+        self.sanitize_into(root, &mut nodes);
+        if nodes.len() == 1 {
+            nodes.swap_remove(0)
+        } else {
+            dom::elem(String::from("div"), AttrMap::new(), nodes)
+        }
+    }
+
+    fn sanitize_into(&self, node: &Node, out: &mut Vec<Node>) {
+        match &node.node_type {
+            NodeType::Text(data) => out.push(dom::text(data.clone())),
+            NodeType::Comment(data) => out.push(dom::comment(data.clone())),
+            NodeType::Doctype(data) => out.push(dom::doctype(data.clone())),
+            NodeType::Element(data) => {
+                let tag = data.tag_name.to_ascii_lowercase();
+                if self.allowed_tags.contains(&tag) {
+                    let mut attrs = AttrMap::new();
+                    for (name, value) in &data.attributes {
+                        if let Some((name, value)) = self.sanitize_attr(&tag, name, value) {
+                            attrs.insert(name, value);
+                        }
+                    }
+                    let mut children = Vec::new();
+                    for child in &node.children {
+                        self.sanitize_into(child, &mut children);
+                    }
+                    out.push(dom::elem(data.tag_name.clone(), attrs, children));
+                } else if self.promote_disallowed_children && !is_raw_content_tag(&tag) {
+                    for child in &node.children {
+                        self.sanitize_into(child, out);
+                    }
+                }
+                // プロモーションが無効な場合や script/style の場合、
+                // 要素とその子孫はまるごと捨てられる
+            }
+        }
+    }
+
+    fn sanitize_attr(&self, tag: &str, name: &str, value: &str) -> Option<(String, String)> {
+        let key = name.to_ascii_lowercase();
+        if !self.is_attr_allowed(tag, &key) {
+            return None;
+        }
+
+        if (key == "href" || key == "src") && !self.scheme_allowed(value) {
+            return None;
+        }
+
+        if key == "src" && self.neutralize_src {
+            return Some((String::from("data-src"), value.to_string()));
+        }
+
+        Some((name.to_string(), value.to_string()))
+    }
+
+    fn is_attr_allowed(&self, tag: &str, attr: &str) -> bool {
+        if self.global_attrs.contains(attr) {
+            return true;
+        }
+        self.allowed_attrs
+            .get(tag)
+            .is_some_and(|attrs| attrs.contains(attr))
+    }
+
+    fn scheme_allowed(&self, value: &str) -> bool {
+        // ブラウザはスキーム判定の前にASCIIタブ/改行をURL全体から取り除く
+        // (WHATWG URL: "remove all ASCII tab or newline") ので、そうしない限り
+        // `java\tscript:` のような難読化でフィルタを回避できてしまう
+        let stripped: String = value
+            .chars()
+            .filter(|c| !matches!(c, '\t' | '\n' | '\r'))
+            .collect();
+        match extract_scheme(&stripped) {
+            Some(scheme) => self.allowed_schemes.contains(&scheme.to_ascii_lowercase()),
+            // 相対URL (スキームなし) はそのまま許可する
+            None => true,
+        }
+    }
+}
+
+impl Default for Sanitizer {
+    // 基本的なテキスト整形用タグだけを許可する既定のポリシー
+    fn default() -> Self {
+        SanitizerBuilder::new()
+            .allow_tag("p")
+            .allow_tag("br")
+            .allow_tag("b")
+            .allow_tag("strong")
+            .allow_tag("i")
+            .allow_tag("em")
+            .allow_tag("u")
+            .allow_tag("span")
+            .allow_tag("a")
+            .allow_tag("ul")
+            .allow_tag("ol")
+            .allow_tag("li")
+            .allow_tag("blockquote")
+            .allow_tag("code")
+            .allow_tag("pre")
+            .allow_tag("h1")
+            .allow_tag("h2")
+            .allow_tag("h3")
+            .allow_tag("h4")
+            .allow_tag("h5")
+            .allow_tag("h6")
+            .allow_global_attr("title")
+            .allow_attr("a", "href")
+            .allow_scheme("http")
+            .allow_scheme("https")
+            .allow_scheme("mailto")
+            .promote_disallowed_children(true)
+            .build()
+    }
+}
+
+// `Sanitizer` を組み立てるビルダー
+pub struct SanitizerBuilder {
+    allowed_tags: HashSet<String>,
+    allowed_attrs: HashMap<String, HashSet<String>>,
+    global_attrs: HashSet<String>,
+    allowed_schemes: HashSet<String>,
+    promote_disallowed_children: bool,
+    neutralize_src: bool,
+}
+
+impl SanitizerBuilder {
+    pub fn new() -> Self {
+        SanitizerBuilder {
+            allowed_tags: HashSet::new(),
+            allowed_attrs: HashMap::new(),
+            global_attrs: HashSet::new(),
+            allowed_schemes: HashSet::new(),
+            promote_disallowed_children: false,
+            neutralize_src: false,
+        }
+    }
+
+    pub fn allow_tag(mut self, tag: &str) -> Self {
+        self.allowed_tags.insert(tag.to_ascii_lowercase());
+        self
+    }
+
+    pub fn allow_attr(mut self, tag: &str, attr: &str) -> Self {
+        self.allowed_attrs
+            .entry(tag.to_ascii_lowercase())
+            .or_default()
+            .insert(attr.to_ascii_lowercase());
+        self
+    }
+
+    // すべての許可タグに対して使える属性 (class, title など)
+    pub fn allow_global_attr(mut self, attr: &str) -> Self {
+        self.global_attrs.insert(attr.to_ascii_lowercase());
+        self
+    }
+
+    pub fn allow_scheme(mut self, scheme: &str) -> Self {
+        self.allowed_schemes.insert(scheme.to_ascii_lowercase());
+        self
+    }
+
+    // 許可されないタグに出会ったとき、その子要素を親の位置に昇格させて残すか
+    pub fn promote_disallowed_children(mut self, promote: bool) -> Self {
+        self.promote_disallowed_children = promote;
+        self
+    }
+
+    // 画像を自動読み込みさせないため、`src` を `data-src` にリネームするか
+    pub fn neutralize_src(mut self, neutralize: bool) -> Self {
+        self.neutralize_src = neutralize;
+        self
+    }
+
+    pub fn build(self) -> Sanitizer {
+        Sanitizer {
+            allowed_tags: self.allowed_tags,
+            allowed_attrs: self.allowed_attrs,
+            global_attrs: self.global_attrs,
+            allowed_schemes: self.allowed_schemes,
+            promote_disallowed_children: self.promote_disallowed_children,
+            neutralize_src: self.neutralize_src,
+        }
+    }
+}
+
+impl Default for SanitizerBuilder {
+    fn default() -> Self {
+        SanitizerBuilder::new()
+    }
+}
+
+// 昇格の対象外: 中身がユーザーに見せるテキストではないタグ
+fn is_raw_content_tag(tag: &str) -> bool {
+    matches!(tag, "script" | "style")
+}
+
+// `value` の先頭にあるURLスキーム (例: "javascript") を取り出す。相対URLなら None
+fn extract_scheme(value: &str) -> Option<&str> {
+    let trimmed = value.trim_start();
+    let colon = trimmed.find(':')?;
+    let scheme = &trimmed[..colon];
+
+    let mut chars = scheme.chars();
+    let first = chars.next()?;
+    if !first.is_ascii_alphabetic() {
+        return None;
+    }
+    if !chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.')) {
+        return None;
+    }
+
+    Some(scheme)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn href_survives(value: &str) -> bool {
+        Sanitizer::default().scheme_allowed(value)
+    }
+
+    #[test]
+    fn rejects_plain_javascript_scheme() {
+        assert!(!href_survives("javascript:alert(1)"));
+    }
+
+    #[test]
+    fn rejects_javascript_scheme_obfuscated_with_tab_or_newline() {
+        assert!(!href_survives("java\tscript:alert(1)"));
+        assert!(!href_survives("java\nscript:alert(1)"));
+        assert!(!href_survives("java\rscript:alert(1)"));
+    }
+
+    #[test]
+    fn allows_plain_relative_and_https_urls() {
+        assert!(href_survives("/path/page.html"));
+        assert!(href_survives("https://example.com"));
+    }
+}